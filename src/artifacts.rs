@@ -0,0 +1,61 @@
+use std::fs::File;
+use std::path::Path;
+
+use tar::Builder as TarBuilder;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Collects the files matched by `patterns` (glob patterns relative to the
+/// working dir) into `run_dir/<task_name>/`, then archives that directory
+/// into a `.tar.gz` alongside it. Logs what was captured; a no-op when
+/// `patterns` is empty.
+pub fn collect(task_name: &str, patterns: &[String], run_dir: &Path) -> Result<(), Error> {
+    if patterns.is_empty() {
+        return Ok(());
+    }
+
+    let dest = run_dir.join(task_name);
+    std::fs::create_dir_all(&dest)?;
+
+    let mut collected = 0;
+    for pattern in patterns {
+        for entry in glob::glob(pattern)? {
+            let path = entry?;
+            if !path.is_file() {
+                continue;
+            }
+            let file_name = path
+                .file_name()
+                .ok_or_else(|| format!("artifact path has no file name: {}", path.display()))?;
+            std::fs::copy(&path, dest.join(file_name))?;
+            collected += 1;
+        }
+    }
+
+    println!(
+        "Collected {collected} artifact(s) for \"{task_name}\" into {}",
+        dest.display()
+    );
+
+    if collected > 0 {
+        archive(&dest, run_dir, task_name)?;
+    }
+
+    Ok(())
+}
+
+fn archive(dest: &Path, run_dir: &Path, task_name: &str) -> Result<(), Error> {
+    let tar_path = run_dir.join(format!("{task_name}.tar.gz"));
+    let tar_gz = File::create(&tar_path)?;
+    let encoder = flate2::write::GzEncoder::new(tar_gz, flate2::Compression::default());
+    let mut tar = TarBuilder::new(encoder);
+    tar.append_dir_all(".", dest)?;
+    tar.finish()?;
+
+    println!(
+        "Archived artifacts for \"{task_name}\" to {}",
+        tar_path.display()
+    );
+
+    Ok(())
+}
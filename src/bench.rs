@@ -0,0 +1,122 @@
+use std::path::Path;
+
+use serde::Serialize;
+use tokio_util::sync::CancellationToken;
+
+use crate::process;
+use crate::Task;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Environment captured alongside a bench run so results can be compared
+/// across machines/commits later.
+#[derive(Serialize, Debug)]
+pub struct EnvInfo {
+    pub os: String,
+    pub cpu: String,
+    pub git_commit: String,
+    pub runner_version: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct TaskBenchResult {
+    pub name: String,
+    pub iterations: usize,
+    pub min_ms: f64,
+    pub median_ms: f64,
+    pub max_ms: f64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BenchReport {
+    pub env: EnvInfo,
+    pub tasks: Vec<TaskBenchResult>,
+}
+
+/// Runs each task `warmups` times (discarded) then `iterations` times,
+/// timing each `run_task_inner` call, and returns min/median/max per task
+/// plus the captured environment. Stops after the in-flight task when
+/// `cancellation_token` fires, so a Ctrl-C during a long bench reaps the
+/// spawned child instead of leaving the process to the default SIGINT
+/// disposition.
+pub async fn run(
+    tasks: Vec<Task>,
+    iterations: usize,
+    warmups: usize,
+    cancellation_token: CancellationToken,
+) -> BenchReport {
+    let env = capture_env();
+    let mut results = Vec::with_capacity(tasks.len());
+
+    'tasks: for task in tasks {
+        for _ in 0..warmups {
+            if cancellation_token.is_cancelled() {
+                break 'tasks;
+            }
+            process::run_task_inner(task.clone(), cancellation_token.clone()).await;
+        }
+
+        let mut durations_ms = Vec::with_capacity(iterations);
+        for _ in 0..iterations {
+            if cancellation_token.is_cancelled() {
+                break 'tasks;
+            }
+            let outcome = process::run_task_inner(task.clone(), cancellation_token.clone()).await;
+            durations_ms.push(outcome.duration.as_secs_f64() * 1000.0);
+        }
+
+        durations_ms.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        results.push(TaskBenchResult {
+            name: task.name,
+            iterations,
+            min_ms: durations_ms.first().copied().unwrap_or(0.0),
+            median_ms: durations_ms.get(durations_ms.len() / 2).copied().unwrap_or(0.0),
+            max_ms: durations_ms.last().copied().unwrap_or(0.0),
+        });
+    }
+
+    BenchReport { env, tasks: results }
+}
+
+fn capture_env() -> EnvInfo {
+    let git_commit = std::process::Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    EnvInfo {
+        os: std::env::consts::OS.to_string(),
+        cpu: std::env::consts::ARCH.to_string(),
+        git_commit,
+        runner_version: env!("CARGO_PKG_VERSION").to_string(),
+    }
+}
+
+/// Prints a human-readable table summarizing a `BenchReport`.
+pub fn print_summary(report: &BenchReport) {
+    println!(
+        "OS: {}  CPU: {}  commit: {}  runner v{}\n",
+        report.env.os, report.env.cpu, report.env.git_commit, report.env.runner_version
+    );
+
+    println!(
+        "{:<24} {:>8} {:>10} {:>10} {:>10}",
+        "task", "iters", "min(ms)", "median(ms)", "max(ms)"
+    );
+    for task in &report.tasks {
+        println!(
+            "{:<24} {:>8} {:>10.2} {:>10.2} {:>10.2}",
+            task.name, task.iterations, task.min_ms, task.median_ms, task.max_ms
+        );
+    }
+}
+
+/// Writes a `BenchReport` as pretty-printed JSON to `path`.
+pub fn write_json(report: &BenchReport, path: &Path) -> Result<(), Error> {
+    let file = std::fs::File::create(path)?;
+    serde_json::to_writer_pretty(file, report)?;
+    Ok(())
+}
@@ -0,0 +1,316 @@
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+
+use crate::artifacts;
+use crate::notifier::TaskOutcome;
+use crate::process;
+use crate::remote;
+use crate::Task;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Summary of a scheduled run, split by how each task ended up.
+#[derive(Debug, Default)]
+pub struct ScheduleResult {
+    pub succeeded: Vec<String>,
+    pub failed: Vec<String>,
+    pub blocked: Vec<String>,
+}
+
+/// Builds the dependency DAG for `tasks`, checks it for cycles, then runs tasks
+/// so a node only spawns once all of its `depends_on` have completed
+/// successfully. Independent subtrees run concurrently. A task whose
+/// dependency failed (or was itself blocked) is skipped and reported as
+/// blocked in the returned summary rather than being run.
+pub async fn run(
+    tasks: Vec<Task>,
+    cancellation_token: CancellationToken,
+    outcomes_tx: tokio::sync::mpsc::UnboundedSender<TaskOutcome>,
+    run_dir: PathBuf,
+    connect: Option<String>,
+    token: Option<String>,
+) -> Result<ScheduleResult, Error> {
+    detect_cycle(&tasks)?;
+
+    let mut remaining: HashMap<String, Task> =
+        tasks.into_iter().map(|t| (t.name.clone(), t)).collect();
+    let mut succeeded: HashSet<String> = HashSet::new();
+    let mut failed: HashSet<String> = HashSet::new();
+    let mut blocked: HashSet<String> = HashSet::new();
+    let mut running: HashSet<String> = HashSet::new();
+    let mut set: JoinSet<(String, bool)> = JoinSet::new();
+
+    loop {
+        let ready: Vec<Task> = remaining
+            .values()
+            .filter(|t| !running.contains(&t.name))
+            .filter(|t| {
+                t.depends_on
+                    .iter()
+                    .all(|dep| succeeded.contains(dep) || failed.contains(dep) || blocked.contains(dep))
+            })
+            .cloned()
+            .collect();
+
+        for task in ready {
+            let is_blocked = task
+                .depends_on
+                .iter()
+                .any(|dep| failed.contains(dep) || blocked.contains(dep));
+
+            if is_blocked {
+                println!("Task \"{}\" blocked (dependency failed)", task.name);
+                blocked.insert(task.name.clone());
+                remaining.remove(&task.name);
+                continue;
+            }
+
+            remaining.remove(&task.name);
+            running.insert(task.name.clone());
+
+            let name = task.name.clone();
+            let artifact_patterns = task.artifacts.clone();
+            let run_dir = run_dir.clone();
+            let connect = connect.clone();
+            let token = token.clone();
+            let cancellation_token = cancellation_token.clone();
+            let outcomes_tx = outcomes_tx.clone();
+            set.spawn(async move {
+                tokio::select! {
+                    _ = cancellation_token.cancelled() => {
+                        println!("Task cancelled {}", name);
+                        (name, false)
+                    }
+                    outcome = run_task(task, connect.clone(), token, cancellation_token.clone()) => {
+                        let success = outcome.success;
+                        if success {
+                            if connect.is_some() {
+                                println!(
+                                    "Skipping artifact collection for \"{name}\": task ran remotely via --connect, local globs wouldn't see its output"
+                                );
+                            } else if let Err(err) = artifacts::collect(&name, &artifact_patterns, &run_dir) {
+                                eprintln!("failed to collect artifacts for \"{name}\": {err}");
+                            }
+                        }
+                        let _ = outcomes_tx.send(outcome);
+                        (name, success)
+                    }
+                }
+            });
+        }
+
+        if set.is_empty() {
+            break;
+        }
+
+        if let Some(Ok((name, success))) = set.join_next().await {
+            running.remove(&name);
+            if success {
+                succeeded.insert(name);
+            } else {
+                failed.insert(name);
+            }
+        }
+    }
+
+    // Anything still in `remaining` depends on a task that never became ready
+    // (e.g. it depends on another blocked task that was only just marked).
+    for name in remaining.into_keys() {
+        blocked.insert(name);
+    }
+
+    Ok(ScheduleResult {
+        succeeded: succeeded.into_iter().collect(),
+        failed: failed.into_iter().collect(),
+        blocked: blocked.into_iter().collect(),
+    })
+}
+
+/// Runs a single task, dispatching to a `runner serve` instance when `connect`
+/// is set instead of spawning a local `sh`.
+async fn run_task(
+    task: Task,
+    connect: Option<String>,
+    token: Option<String>,
+    cancellation_token: CancellationToken,
+) -> TaskOutcome {
+    match connect {
+        Some(host) => remote::run_task_inner(task, &host, token, cancellation_token).await,
+        None => process::run_task_inner(task, cancellation_token).await,
+    }
+}
+
+/// Walks the dependency graph looking for cycles, returning an error naming
+/// the first one found (e.g. `a -> b -> a`).
+fn detect_cycle(tasks: &[Task]) -> Result<(), Error> {
+    let by_name: HashMap<&str, &Task> = tasks.iter().map(|t| (t.name.as_str(), t)).collect();
+
+    #[derive(PartialEq)]
+    enum Mark {
+        InProgress,
+        Done,
+    }
+
+    let mut marks: HashMap<&str, Mark> = HashMap::new();
+
+    fn visit<'a>(
+        name: &'a str,
+        by_name: &HashMap<&'a str, &'a Task>,
+        marks: &mut HashMap<&'a str, Mark>,
+        stack: &mut Vec<&'a str>,
+    ) -> Result<(), Error> {
+        if let Some(Mark::Done) = marks.get(name) {
+            return Ok(());
+        }
+        if let Some(Mark::InProgress) = marks.get(name) {
+            stack.push(name);
+            let start = stack.iter().position(|n| *n == name).unwrap_or(0);
+            return Err(format!("cycle detected in depends_on: {}", stack[start..].join(" -> ")).into());
+        }
+
+        let Some(task) = by_name.get(name) else {
+            return Ok(());
+        };
+
+        marks.insert(name, Mark::InProgress);
+        stack.push(name);
+
+        for dep in &task.depends_on {
+            visit(dep, by_name, marks, stack)?;
+        }
+
+        stack.pop();
+        marks.insert(name, Mark::Done);
+        Ok(())
+    }
+
+    for name in by_name.keys() {
+        let mut stack = Vec::new();
+        visit(name, &by_name, &mut marks, &mut stack)?;
+    }
+
+    Ok(())
+}
+
+/// Prints a human-readable summary of a scheduled run's outcome.
+pub fn print_summary(result: &ScheduleResult) {
+    println!(
+        "\nSummary: {} succeeded, {} failed, {} blocked",
+        result.succeeded.len(),
+        result.failed.len(),
+        result.blocked.len()
+    );
+
+    if !result.failed.is_empty() {
+        println!("  failed: {}", result.failed.join(", "));
+    }
+    if !result.blocked.is_empty() {
+        println!("  blocked: {}", result.blocked.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(name: &str, cmd: &str, depends_on: &[&str]) -> Task {
+        Task {
+            name: name.to_string(),
+            cmd: cmd.to_string(),
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            artifacts: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn detects_two_node_cycle() {
+        let tasks = vec![task("a", "true", &["b"]), task("b", "true", &["a"])];
+
+        let err = detect_cycle(&tasks).expect_err("should detect a -> b -> a");
+        assert!(err.to_string().contains("cycle detected"));
+    }
+
+    #[test]
+    fn detects_self_cycle() {
+        let tasks = vec![task("a", "true", &["a"])];
+
+        let err = detect_cycle(&tasks).expect_err("should detect a -> a");
+        assert!(err.to_string().contains("cycle detected"));
+    }
+
+    #[test]
+    fn allows_dag_with_shared_dependency() {
+        let tasks = vec![
+            task("a", "true", &[]),
+            task("b", "true", &["a"]),
+            task("c", "true", &["a"]),
+        ];
+
+        assert!(detect_cycle(&tasks).is_ok());
+    }
+
+    #[test]
+    fn unknown_dependency_name_is_not_a_cycle() {
+        let tasks = vec![task("a", "true", &["does-not-exist"])];
+
+        assert!(detect_cycle(&tasks).is_ok());
+    }
+
+    #[tokio::test]
+    async fn three_level_chain_blocks_after_root_failure() {
+        let tasks = vec![
+            task("a", "false", &[]),
+            task("b", "true", &["a"]),
+            task("c", "true", &["b"]),
+        ];
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let result = run(
+            tasks,
+            CancellationToken::new(),
+            tx,
+            std::env::temp_dir().join("runner-dag-test"),
+            None,
+            None,
+        )
+        .await
+        .expect("no cycle");
+
+        assert_eq!(result.failed, vec!["a".to_string()]);
+        let mut blocked = result.blocked;
+        blocked.sort();
+        assert_eq!(blocked, vec!["b".to_string(), "c".to_string()]);
+        assert!(result.succeeded.is_empty());
+
+        // `a` is the only task that actually ran, so it's the only outcome sent.
+        let outcome = rx.recv().await.expect("outcome for the root task");
+        assert_eq!(outcome.name, "a");
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn independent_tasks_all_succeed() {
+        let tasks = vec![task("a", "true", &[]), task("b", "true", &[])];
+
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let result = run(
+            tasks,
+            CancellationToken::new(),
+            tx,
+            std::env::temp_dir().join("runner-dag-test"),
+            None,
+            None,
+        )
+        .await
+        .expect("no cycle");
+
+        let mut succeeded = result.succeeded;
+        succeeded.sort();
+        assert_eq!(succeeded, vec!["a".to_string(), "b".to_string()]);
+        assert!(result.failed.is_empty());
+        assert!(result.blocked.is_empty());
+    }
+}
@@ -0,0 +1,63 @@
+use bb8::Pool;
+use bb8_postgres::PostgresConnectionManager;
+use tokio_postgres::NoTls;
+
+use crate::notifier::TaskOutcome;
+
+type Error = Box<dyn std::error::Error>;
+
+pub type DbPool = Pool<PostgresConnectionManager<NoTls>>;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS task_runs (
+    id BIGSERIAL PRIMARY KEY,
+    name TEXT NOT NULL,
+    cmd TEXT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL,
+    ended_at TIMESTAMPTZ NOT NULL,
+    success BOOLEAN NOT NULL,
+    stderr_tail TEXT NOT NULL
+)";
+
+/// Connects to Postgres via a `bb8` pool and makes sure the `task_runs` table
+/// exists. Called once at startup when `--db-url`/`RUNNER_DB_URL` is set.
+pub async fn connect(db_url: &str) -> Result<DbPool, Error> {
+    let manager = PostgresConnectionManager::new_from_stringlike(db_url, NoTls)?;
+    let pool = Pool::builder().build(manager).await?;
+
+    let conn = pool.get().await?;
+    conn.batch_execute(SCHEMA).await?;
+    drop(conn);
+
+    Ok(pool)
+}
+
+/// Consumes task outcomes from `rx` and writes one row per completion into
+/// `task_runs`. Runs for the lifetime of the process once spawned.
+pub async fn dispatch(pool: DbPool, mut rx: tokio::sync::mpsc::UnboundedReceiver<TaskOutcome>) {
+    while let Some(outcome) = rx.recv().await {
+        if let Err(err) = record(&pool, &outcome).await {
+            eprintln!("failed to persist run history for \"{}\": {err}", outcome.name);
+        }
+    }
+}
+
+async fn record(pool: &DbPool, outcome: &TaskOutcome) -> Result<(), Error> {
+    let conn = pool.get().await?;
+
+    conn.execute(
+        "INSERT INTO task_runs (name, cmd, started_at, ended_at, success, stderr_tail)
+         VALUES ($1, $2, $3, $4, $5, $6)",
+        &[
+            &outcome.name,
+            &outcome.cmd,
+            &outcome.started_at,
+            &outcome.ended_at,
+            &outcome.success,
+            &outcome.stderr_tail,
+        ],
+    )
+    .await?;
+
+    Ok(())
+}
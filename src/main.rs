@@ -1,10 +1,20 @@
-use std::{fs::File, io::BufReader, process::Stdio, sync::Arc};
-use tokio::{process::Command, task::JoinSet};
+use std::{fs::File, io::BufReader, sync::Arc};
 
 use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 use tokio_util::sync::CancellationToken;
 
+mod artifacts;
+mod bench;
+mod dag;
+mod db;
+mod notifier;
+mod process;
+mod remote;
+mod script;
+
+use notifier::{NotifierConfig, TaskOutcome};
+
 type Error = Box<dyn std::error::Error>;
 
 const RUNNER_JSON: &str = "runner.json";
@@ -13,59 +23,59 @@ const RUNNER_JSON: &str = "runner.json";
 struct Task {
     name: String,
     cmd: String,
+    #[serde(default)]
+    depends_on: Vec<String>,
+    /// Glob patterns (relative to the working dir) of files to collect after
+    /// this task succeeds. Typically set on `builds` entries.
+    #[serde(default)]
+    artifacts: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct Runner {
     tasks: Option<Vec<Task>>,
     builds: Option<Vec<Task>>,
+    #[serde(default)]
+    notifiers: Option<Vec<NotifierConfig>>,
 }
 
 struct InternalRunner {
     tasks: Option<Arc<tokio::sync::Mutex<Vec<Task>>>>,
     builds: Option<Arc<tokio::sync::Mutex<Vec<Task>>>>,
+    notifiers: Vec<NotifierConfig>,
+    db_pool: Option<db::DbPool>,
+    connect: Option<String>,
+    token: Option<String>,
 }
 
 impl InternalRunner {
     async fn build_all(&self) {
-        let cancellation_token = CancellationToken::new();
-        let _cancellation_token = cancellation_token.clone();
-
         println!("Building all builds\n");
-        let mut set = JoinSet::new();
-
-        if let Some(builds) = &self.builds {
-            for build in builds.lock().await.iter() {
-                self.run_task(build.clone(), cancellation_token.clone(), &mut set)
-                    .await;
-            }
-        }
 
-        ctrlc::set_handler(move || {
-            println!("Exiting...");
-            cancellation_token.cancel();
-        })
-        .expect("Error setting Ctrl-C handler");
+        let Some(builds) = &self.builds else {
+            return;
+        };
+        let builds = builds.lock().await.clone();
+        self.run_scheduled(builds).await;
+    }
 
-        while let Some(_) = set.join_next().await {}
+    async fn run_all(&self) {
+        println!("Running all tasks\n");
 
-        // everything is done
-        _cancellation_token.cancel();
+        let Some(tasks) = &self.tasks else {
+            return;
+        };
+        let tasks = tasks.lock().await.clone();
+        self.run_scheduled(tasks).await;
     }
 
-    async fn run_all(&self) {
+    /// Schedules `tasks` over the dependency DAG and prints the final summary.
+    async fn run_scheduled(&self, tasks: Vec<Task>) {
         let cancellation_token = CancellationToken::new();
         let _cancellation_token = cancellation_token.clone();
-        let mut set = JoinSet::new();
-
-        println!("Running all tasks\n");
-
-        if let Some(tasks) = &self.tasks {
-            for task in tasks.lock().await.iter() {
-                self.run_task(task.clone(), cancellation_token.clone(), &mut set)
-                    .await;
-            }
-        }
+        let outcomes_tx = self.spawn_outcome_relay();
+        let run_dir = std::path::PathBuf::from("runner-artifacts")
+            .join(chrono::Utc::now().format("%Y%m%d-%H%M%S").to_string());
 
         ctrlc::set_handler(move || {
             println!("Exiting...");
@@ -73,63 +83,83 @@ impl InternalRunner {
         })
         .expect("Error setting Ctrl-C handler");
 
-        while let Some(_) = set.join_next().await {}
+        match dag::run(
+            tasks,
+            _cancellation_token.clone(),
+            outcomes_tx,
+            run_dir,
+            self.connect.clone(),
+            self.token.clone(),
+        )
+        .await
+        {
+            Ok(result) => dag::print_summary(&result),
+            Err(err) => eprintln!("{err}"),
+        }
 
         // everything is done
         _cancellation_token.cancel();
     }
 
-    async fn run_task(
-        &self,
-        task: Task,
-        _cancellation_token: CancellationToken,
-        set: &mut JoinSet<()>,
-    ) {
-        let _cancellation_token = _cancellation_token.clone();
-        set.spawn(async move {
-            loop {
-                let rate = tokio::time::Duration::from_millis(1000);
-                tokio::time::sleep(rate).await;
-                tokio::select! {
-                    _ = _cancellation_token.cancelled() => {
-                        println!("Task cancelled {}", task.name);
-                        break;
-                    },
-                    _ = run_task_inner(task.clone()) => {
-                        break;
-                    }
+    /// Spawns the notifier dispatcher (and, if configured, the history-db
+    /// dispatcher) for this run, and returns a sender that the scheduler feeds
+    /// with each task's final outcome. Every outcome is relayed to both.
+    fn spawn_outcome_relay(&self) -> tokio::sync::mpsc::UnboundedSender<TaskOutcome> {
+        let (notify_tx, notify_rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(notifier::dispatch(self.notifiers.clone(), notify_rx));
+
+        let db_tx = self.db_pool.clone().map(|pool| {
+            let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+            tokio::spawn(db::dispatch(pool, rx));
+            tx
+        });
+
+        let (relay_tx, mut relay_rx) = tokio::sync::mpsc::unbounded_channel::<TaskOutcome>();
+        tokio::spawn(async move {
+            while let Some(outcome) = relay_rx.recv().await {
+                if let Some(db_tx) = &db_tx {
+                    let _ = db_tx.send(outcome.clone());
                 }
+                let _ = notify_tx.send(outcome);
             }
         });
+
+        relay_tx
     }
-}
 
-async fn run_task_inner(task: Task) {
-    let mut cmd = Command::new("sh");
-    cmd.arg("-c")
-        .arg(&task.cmd)
-        .kill_on_drop(true)
-        .stdout(Stdio::piped())
-        .spawn()
-        .expect("failed to execute process");
-
-    println!("Running task: \"{}\"", task.name);
-
-    let output = cmd.output().await.expect("failed to execute process");
-
-    if output.status.success() {
-        println!("\nTask: \"{}\" succeeded", task.name);
-        println!("Task succeeded");
-        println!("Output: {}", String::from_utf8_lossy(&output.stdout));
-    } else {
-        println!("\nTask \"{}\" failed", task.name);
-        println!("Stderr: {}", String::from_utf8_lossy(&output.stderr));
+    /// Times `tasks` over `iterations` runs (after `warmups` discarded ones)
+    /// and writes both a console summary and a `bench_output.txt` JSON report.
+    async fn bench(&self, iterations: usize, warmups: usize) {
+        let Some(tasks) = &self.tasks else {
+            println!("No tasks configured to bench");
+            return;
+        };
+        let tasks = tasks.lock().await.clone();
+
+        let cancellation_token = CancellationToken::new();
+        let ctrlc_token = cancellation_token.clone();
+        ctrlc::set_handler(move || {
+            println!("Exiting...");
+            ctrlc_token.cancel();
+        })
+        .expect("Error setting Ctrl-C handler");
+
+        let report = bench::run(tasks, iterations, warmups, cancellation_token).await;
+        bench::print_summary(&report);
+
+        if let Err(err) = bench::write_json(&report, std::path::Path::new("bench_output.txt")) {
+            eprintln!("failed to write bench report: {err}");
+        }
     }
 }
 
 impl Runner {
-    fn init() -> Result<InternalRunner, Error> {
-        if let Ok(runner) = Runner::init_runner(RUNNER_JSON) {
+    fn init(
+        db_pool: Option<db::DbPool>,
+        connect: Option<String>,
+        token: Option<String>,
+    ) -> Result<InternalRunner, Error> {
+        if let Ok(runner) = Runner::load_config() {
             let tasks = if let Some(tasks) = runner.tasks {
                 Some(Arc::new(tokio::sync::Mutex::new(tasks)))
             } else {
@@ -142,7 +172,19 @@ impl Runner {
                 None
             };
 
-            Ok(InternalRunner { tasks, builds })
+            let notifiers = runner.notifiers.unwrap_or_default();
+            for notifier in &notifiers {
+                notifier.validate()?;
+            }
+
+            Ok(InternalRunner {
+                tasks,
+                builds,
+                notifiers,
+                db_pool,
+                connect,
+                token,
+            })
         } else {
             Err("Failed to initialize runner".into())
         }
@@ -154,6 +196,38 @@ impl Runner {
 
         Ok(serde_json::from_reader(reader)?)
     }
+
+    /// Loads `runner.json` (if present) and merges in any tasks/builds
+    /// produced by evaluating `runner.lua` (if present). Either file alone is
+    /// enough; having neither is an error.
+    fn load_config() -> Result<Runner, Error> {
+        let mut runner = Runner::init_runner(RUNNER_JSON).unwrap_or(Runner {
+            tasks: None,
+            builds: None,
+            notifiers: None,
+        });
+
+        let lua_path = std::path::Path::new(script::RUNNER_LUA);
+        if lua_path.exists() {
+            let (lua_tasks, lua_builds) = script::load(lua_path)?;
+
+            if !lua_tasks.is_empty() {
+                runner.tasks.get_or_insert_with(Vec::new).extend(lua_tasks);
+            }
+            if !lua_builds.is_empty() {
+                runner
+                    .builds
+                    .get_or_insert_with(Vec::new)
+                    .extend(lua_builds);
+            }
+        }
+
+        if runner.tasks.is_none() && runner.builds.is_none() {
+            return Err("Failed to initialize runner".into());
+        }
+
+        Ok(runner)
+    }
 }
 
 #[derive(Subcommand)]
@@ -161,6 +235,23 @@ enum Commands {
     Run,
     R,
     Build,
+    /// Runs tasks repeatedly and reports timing statistics.
+    Bench {
+        /// Number of timed iterations per task.
+        #[arg(long, default_value_t = 10)]
+        iterations: usize,
+        /// Untimed warmup runs per task, discarded before timing starts.
+        #[arg(long, default_value_t = 2)]
+        warmups: usize,
+    },
+    /// Runs as a remote execution server that `--connect` clients dispatch to.
+    Serve {
+        /// Address to listen on, e.g. "127.0.0.1:7878". Defaults to localhost
+        /// only; bind to a wider address explicitly (and set `--token`) to
+        /// accept connections from other hosts.
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        addr: String,
+    },
 }
 
 #[derive(Parser)]
@@ -168,6 +259,23 @@ enum Commands {
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    /// Postgres connection string to persist run history into. When unset,
+    /// behavior is unchanged and nothing is written to a database.
+    #[arg(long, env = "RUNNER_DB_URL")]
+    db_url: Option<String>,
+
+    /// Address of a `runner serve` instance to dispatch tasks/builds to
+    /// instead of running them locally, e.g. "127.0.0.1:7878".
+    #[arg(long)]
+    connect: Option<String>,
+
+    /// Shared secret required from clients by `runner serve`, and sent by
+    /// `--connect` clients to authenticate. Without this, `serve` accepts
+    /// any task any peer that can reach it sends — only skip it on a
+    /// loopback-only or otherwise trusted address.
+    #[arg(long, env = "RUNNER_TOKEN")]
+    token: Option<String>,
 }
 
 impl Cli {
@@ -182,8 +290,27 @@ impl Cli {
 
 #[tokio::main]
 async fn main() {
-    if let Ok(runner) = Runner::init() {
-        let cli = Cli::parse();
+    let cli = Cli::parse();
+
+    let db_pool = match &cli.db_url {
+        Some(db_url) => match db::connect(db_url).await {
+            Ok(pool) => Some(pool),
+            Err(err) => {
+                eprintln!("failed to connect to history database: {err}");
+                None
+            }
+        },
+        None => None,
+    };
+
+    if let Some(Commands::Serve { addr }) = &cli.command {
+        if let Err(err) = remote::serve(addr, cli.token.clone()).await {
+            eprintln!("serve failed: {err}");
+        }
+        return;
+    }
+
+    if let Ok(runner) = Runner::init(db_pool, cli.connect.clone(), cli.token.clone()) {
         Cli::print_version();
 
         match cli.command {
@@ -196,6 +323,10 @@ async fn main() {
             Some(Commands::Build) => {
                 runner.build_all().await;
             }
+            Some(Commands::Bench { iterations, warmups }) => {
+                runner.bench(iterations, warmups).await;
+            }
+            Some(Commands::Serve { .. }) => unreachable!("handled above"),
             None => {
                 println!("No command specified");
             }
@@ -0,0 +1,202 @@
+use std::time::{Duration, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Outcome of a single `run_task_inner` invocation, as reported to the notifier
+/// dispatcher (and, when configured, the run-history database) once the
+/// task's process has exited. `started_at`/`ended_at` are `SystemTime` rather
+/// than a `chrono` type so they can be bound directly as `tokio_postgres`
+/// query params without an extra crate feature.
+#[derive(Debug, Clone)]
+pub struct TaskOutcome {
+    pub name: String,
+    pub cmd: String,
+    pub started_at: SystemTime,
+    pub ended_at: SystemTime,
+    pub success: bool,
+    pub duration: Duration,
+    pub stderr_tail: String,
+}
+
+/// When a configured notifier should fire.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum NotifyOn {
+    #[default]
+    FailureOnly,
+    Always,
+}
+
+/// One entry of the `notifiers` array in `runner.json`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum NotifierConfig {
+    Webhook {
+        url: String,
+        #[serde(default)]
+        notify_on: NotifyOn,
+    },
+    Email {
+        smtp_host: String,
+        smtp_port: u16,
+        username: String,
+        password: String,
+        from: String,
+        to: String,
+        #[serde(default)]
+        notify_on: NotifyOn,
+    },
+}
+
+impl NotifierConfig {
+    /// Validates config that would otherwise only be discovered the first
+    /// time the notifier actually fires (e.g. a malformed `Email` address),
+    /// so a typo in `runner.json` is caught at startup rather than silently
+    /// swallowing the real alert during an unattended run.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let NotifierConfig::Email { from, to, .. } = self {
+            use lettre::message::Mailbox;
+            from.parse::<Mailbox>()
+                .map_err(|err| format!("invalid notifier `from` address {from:?}: {err}"))?;
+            to.parse::<Mailbox>()
+                .map_err(|err| format!("invalid notifier `to` address {to:?}: {err}"))?;
+        }
+        Ok(())
+    }
+
+    fn notify_on(&self) -> NotifyOn {
+        match self {
+            NotifierConfig::Webhook { notify_on, .. } => *notify_on,
+            NotifierConfig::Email { notify_on, .. } => *notify_on,
+        }
+    }
+
+    fn should_fire(&self, outcome: &TaskOutcome) -> bool {
+        match self.notify_on() {
+            NotifyOn::Always => true,
+            NotifyOn::FailureOnly => !outcome.success,
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn Notifier> {
+        match self {
+            NotifierConfig::Webhook { url, .. } => Box::new(WebhookNotifier { url: url.clone() }),
+            NotifierConfig::Email {
+                smtp_host,
+                smtp_port,
+                username,
+                password,
+                from,
+                to,
+                ..
+            } => Box::new(EmailNotifier {
+                smtp_host: smtp_host.clone(),
+                smtp_port: *smtp_port,
+                username: username.clone(),
+                password: password.clone(),
+                from: from.clone(),
+                to: to.clone(),
+            }),
+        }
+    }
+}
+
+/// A backend capable of reporting a `TaskOutcome` somewhere outside the terminal.
+#[async_trait::async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, outcome: &TaskOutcome) -> Result<(), Error>;
+}
+
+struct WebhookNotifier {
+    url: String,
+}
+
+#[async_trait::async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, outcome: &TaskOutcome) -> Result<(), Error> {
+        let body = serde_json::json!({
+            "task": outcome.name,
+            "success": outcome.success,
+            "duration_ms": outcome.duration.as_millis(),
+            "stderr_tail": outcome.stderr_tail,
+        });
+
+        reqwest::Client::new()
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+struct EmailNotifier {
+    smtp_host: String,
+    smtp_port: u16,
+    username: String,
+    password: String,
+    from: String,
+    to: String,
+}
+
+#[async_trait::async_trait]
+impl Notifier for EmailNotifier {
+    async fn notify(&self, outcome: &TaskOutcome) -> Result<(), Error> {
+        use lettre::message::Mailbox;
+        use lettre::transport::smtp::authentication::Credentials;
+        use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+        let from: Mailbox = self.from.parse()?;
+        let to: Mailbox = self.to.parse()?;
+
+        let status = if outcome.success { "succeeded" } else { "failed" };
+        let email = Message::builder()
+            .from(from)
+            .to(to)
+            .subject(format!("Task \"{}\" {status}", outcome.name))
+            .body(format!(
+                "Task: {}\nStatus: {status}\nDuration: {:.2?}\nStderr tail:\n{}",
+                outcome.name, outcome.duration, outcome.stderr_tail
+            ))?;
+
+        let creds = Credentials::new(self.username.clone(), self.password.clone());
+        let mailer: AsyncSmtpTransport<Tokio1Executor> =
+            AsyncSmtpTransport::<Tokio1Executor>::relay(&self.smtp_host)?
+                .port(self.smtp_port)
+                .credentials(creds)
+                .build();
+
+        mailer.send(email).await?;
+
+        Ok(())
+    }
+}
+
+/// Consumes task outcomes from `rx` and fans each one out to every configured
+/// notifier whose `notify_on` setting matches the outcome.
+pub async fn dispatch(
+    configs: Vec<NotifierConfig>,
+    mut rx: tokio::sync::mpsc::UnboundedReceiver<TaskOutcome>,
+) {
+    let notifiers: Vec<(NotifierConfig, Box<dyn Notifier>)> = configs
+        .into_iter()
+        .map(|config| {
+            let notifier = config.build();
+            (config, notifier)
+        })
+        .collect();
+
+    while let Some(outcome) = rx.recv().await {
+        for (config, notifier) in &notifiers {
+            if config.should_fire(&outcome) {
+                if let Err(err) = notifier.notify(&outcome).await {
+                    eprintln!("notifier failed for task \"{}\": {err}", outcome.name);
+                }
+            }
+        }
+    }
+}
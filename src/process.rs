@@ -0,0 +1,204 @@
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::process::Stdio;
+use std::time::{Instant, SystemTime};
+
+use portable_pty::{native_pty_system, CommandBuilder, PtySize};
+use tokio::io::{AsyncBufReadExt, BufReader as TokioBufReader};
+use tokio::process::Command;
+use tokio_util::sync::CancellationToken;
+
+use crate::notifier::TaskOutcome;
+use crate::Task;
+
+pub(crate) const TAIL_LINES: usize = 20;
+
+/// Runs `task.cmd` under a PTY when one can be allocated, streaming stdout/stderr
+/// line-by-line to the console as it arrives instead of buffering until exit.
+/// Falls back to plain piped `Stdio` (no PTY) when the platform can't give us one.
+/// Returns the task's final outcome so callers can forward it to notifiers.
+pub async fn run_task_inner(task: Task, cancellation_token: CancellationToken) -> TaskOutcome {
+    println!("Running task: \"{}\"", task.name);
+    let start = Instant::now();
+    let started_at = SystemTime::now();
+
+    let (success, stderr_tail) = match run_with_pty(&task, cancellation_token.clone()).await {
+        Ok(result) => result,
+        Err(_) => run_with_piped_stdio(&task, cancellation_token).await,
+    };
+
+    report(&task, success);
+
+    TaskOutcome {
+        name: task.name,
+        cmd: task.cmd,
+        started_at,
+        ended_at: SystemTime::now(),
+        success,
+        duration: start.elapsed(),
+        stderr_tail,
+    }
+}
+
+fn report(task: &Task, success: bool) {
+    if success {
+        println!("\nTask: \"{}\" succeeded", task.name);
+    } else {
+        println!("\nTask \"{}\" failed", task.name);
+    }
+}
+
+/// Allocates a pseudo-terminal and runs the task's command inside it, spawning a
+/// blocking thread to pump PTY output into the task's name-prefixed console lines.
+/// PTYs merge stdout/stderr into one stream, so the returned tail covers both.
+async fn run_with_pty(
+    task: &Task,
+    cancellation_token: CancellationToken,
+) -> Result<(bool, String), Error> {
+    let pty_system = native_pty_system();
+    let pair = pty_system.openpty(PtySize {
+        rows: 24,
+        cols: 80,
+        pixel_width: 0,
+        pixel_height: 0,
+    })?;
+
+    let mut cmd = CommandBuilder::new("sh");
+    cmd.arg("-c");
+    cmd.arg(&task.cmd);
+
+    let mut child = pair.slave.spawn_command(cmd)?;
+    drop(pair.slave);
+
+    let mut reader = pair.master.try_clone_reader()?;
+    let name = task.name.clone();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+
+    tokio::task::spawn_blocking(move || {
+        let mut buf = BufReader::new(reader.as_mut());
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match buf.read_line(&mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if tx.send(line.trim_end().to_string()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
+    let mut tail = VecDeque::with_capacity(TAIL_LINES);
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                let _ = child.kill();
+                while let Some(line) = rx.recv().await {
+                    push_tail(&mut tail, &line);
+                    println!("[{name}] {line}");
+                }
+                return Ok((false, tail_to_string(&tail)));
+            }
+            line = rx.recv() => {
+                match line {
+                    Some(line) => {
+                        push_tail(&mut tail, &line);
+                        println!("[{name}] {line}");
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    let status = tokio::task::spawn_blocking(move || child.wait()).await??;
+    Ok((status.success(), tail_to_string(&tail)))
+}
+
+/// Fallback path for platforms/environments where a PTY can't be allocated: runs
+/// the command with plain piped stdio and streams lines as they're read.
+async fn run_with_piped_stdio(
+    task: &Task,
+    cancellation_token: CancellationToken,
+) -> (bool, String) {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(&task.cmd)
+        .kill_on_drop(true)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(_) => {
+            eprintln!("failed to execute process for task \"{}\"", task.name);
+            return (false, String::new());
+        }
+    };
+
+    let stdout = child.stdout.take().expect("stdout was piped");
+    let stderr = child.stderr.take().expect("stderr was piped");
+    let name = task.name.clone();
+    let name_err = task.name.clone();
+
+    let mut stdout_lines = TokioBufReader::new(stdout).lines();
+    let mut stderr_lines = TokioBufReader::new(stderr).lines();
+    let mut tail = VecDeque::with_capacity(TAIL_LINES);
+
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                let _ = child.start_kill();
+                drain(&mut stdout_lines, &name, None).await;
+                drain(&mut stderr_lines, &name_err, Some(&mut tail)).await;
+                let _ = child.wait().await;
+                return (false, tail_to_string(&tail));
+            }
+            line = stdout_lines.next_line() => {
+                if let Ok(Some(line)) = line {
+                    println!("[{name}] {line}");
+                }
+            }
+            line = stderr_lines.next_line() => {
+                if let Ok(Some(line)) = line {
+                    push_tail(&mut tail, &line);
+                    println!("[{name_err}] {line}");
+                }
+            }
+            status = child.wait() => {
+                drain(&mut stdout_lines, &name, None).await;
+                drain(&mut stderr_lines, &name_err, Some(&mut tail)).await;
+                let success = status.map(|s| s.success()).unwrap_or(false);
+                return (success, tail_to_string(&tail));
+            }
+        }
+    }
+}
+
+async fn drain(
+    lines: &mut tokio::io::Lines<TokioBufReader<impl tokio::io::AsyncRead + Unpin>>,
+    name: &str,
+    mut tail: Option<&mut VecDeque<String>>,
+) {
+    while let Ok(Some(line)) = lines.next_line().await {
+        if let Some(tail) = tail.as_deref_mut() {
+            push_tail(tail, &line);
+        }
+        println!("[{name}] {line}");
+    }
+}
+
+pub(crate) fn push_tail(tail: &mut VecDeque<String>, line: &str) {
+    if tail.len() == TAIL_LINES {
+        tail.pop_front();
+    }
+    tail.push_back(line.to_string());
+}
+
+pub(crate) fn tail_to_string(tail: &VecDeque<String>) -> String {
+    tail.iter().cloned().collect::<Vec<_>>().join("\n")
+}
+
+type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+use crate::notifier::TaskOutcome;
+use crate::process::{push_tail, tail_to_string, TAIL_LINES};
+use crate::Task;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Message sent from client to server over a `serve` connection. `Run` carries
+/// the configured `--token`/`RUNNER_TOKEN` (if any) so the server can reject
+/// unauthenticated execution requests before running anything.
+#[derive(Serialize, Deserialize, Debug)]
+enum ClientMessage {
+    Run { task: Task, token: Option<String> },
+    Cancel,
+}
+
+/// Message streamed from server to client: task output as it's produced,
+/// followed by exactly one `Exit`.
+#[derive(Serialize, Deserialize, Debug)]
+enum ServerMessage {
+    Line(String),
+    Exit { success: bool, stderr_tail: String },
+}
+
+/// Runs `runner serve` on `addr`: accepts connections and runs whatever task
+/// each one sends, streaming output back line by line. When `token` is set,
+/// connections whose `Run` message doesn't carry a matching token are refused
+/// before anything is executed.
+pub async fn serve(addr: &str, token: Option<String>) -> Result<(), Error> {
+    let listener = TcpListener::bind(addr).await?;
+    println!("Serving on {addr}");
+
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        println!("Accepted connection from {peer}");
+        let token = token.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, token).await {
+                eprintln!("connection from {peer} ended with error: {err}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(stream: TcpStream, token: Option<String>) -> Result<(), Error> {
+    let (read_half, write_half) = stream.into_split();
+    let mut lines = BufReader::new(read_half).lines();
+    let write_half = Arc::new(Mutex::new(write_half));
+
+    let Some(line) = lines.next_line().await? else {
+        return Ok(());
+    };
+    let task = match serde_json::from_str::<ClientMessage>(&line)? {
+        ClientMessage::Run { task, token: given } => {
+            if token.is_some() && given != token {
+                let mut write_half = write_half.lock().await;
+                send(
+                    &mut write_half,
+                    &ServerMessage::Exit {
+                        success: false,
+                        stderr_tail: "unauthorized: missing or incorrect --token".to_string(),
+                    },
+                )
+                .await?;
+                return Ok(());
+            }
+            task
+        }
+        ClientMessage::Cancel => return Ok(()),
+    };
+
+    let cancellation_token = CancellationToken::new();
+    let cancel_watch = cancellation_token.clone();
+    tokio::spawn(async move {
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Ok(ClientMessage::Cancel) = serde_json::from_str::<ClientMessage>(&line) {
+                cancel_watch.cancel();
+                break;
+            }
+        }
+    });
+
+    let (success, stderr_tail) = run_local(&task, cancellation_token, &write_half).await;
+
+    let mut write_half = write_half.lock().await;
+    send(&mut write_half, &ServerMessage::Exit { success, stderr_tail }).await?;
+
+    Ok(())
+}
+
+/// Runs `task.cmd` locally with piped stdio, forwarding each output line to
+/// the connected client as it's produced.
+async fn run_local(
+    task: &Task,
+    cancellation_token: CancellationToken,
+    write_half: &Arc<Mutex<OwnedWriteHalf>>,
+) -> (bool, String) {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c")
+        .arg(&task.cmd)
+        .kill_on_drop(true)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(err) => return (false, err.to_string()),
+    };
+
+    let stdout = child.stdout.take().expect("stdout was piped");
+    let stderr = child.stderr.take().expect("stderr was piped");
+    let mut stdout_lines = BufReader::new(stdout).lines();
+    let mut stderr_lines = BufReader::new(stderr).lines();
+    let mut stderr_tail = VecDeque::with_capacity(TAIL_LINES);
+
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                let _ = child.start_kill();
+                let _ = child.wait().await;
+                return (false, tail_to_string(&stderr_tail));
+            }
+            line = stdout_lines.next_line() => {
+                if let Ok(Some(line)) = line {
+                    let mut write_half = write_half.lock().await;
+                    let _ = send(&mut write_half, &ServerMessage::Line(line)).await;
+                }
+            }
+            line = stderr_lines.next_line() => {
+                if let Ok(Some(line)) = line {
+                    push_tail(&mut stderr_tail, &line);
+                    let mut write_half = write_half.lock().await;
+                    let _ = send(&mut write_half, &ServerMessage::Line(line)).await;
+                }
+            }
+            status = child.wait() => {
+                let success = status.map(|s| s.success()).unwrap_or(false);
+                return (success, tail_to_string(&stderr_tail));
+            }
+        }
+    }
+}
+
+async fn send(
+    write_half: &mut OwnedWriteHalf,
+    message: &ServerMessage,
+) -> Result<(), Error> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    write_half.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+
+/// Dispatches `task` to the `serve` instance at `host` instead of spawning a
+/// local `sh`, streaming its output to the console and honoring
+/// `cancellation_token` by sending a `Cancel` message over the connection.
+/// `token` is forwarded so the server can authenticate the request.
+pub async fn run_task_inner(
+    task: Task,
+    host: &str,
+    token: Option<String>,
+    cancellation_token: CancellationToken,
+) -> TaskOutcome {
+    println!("Running task: \"{}\" on {host}", task.name);
+    let start = Instant::now();
+    let started_at = SystemTime::now();
+
+    match dispatch(&task, host, token, cancellation_token).await {
+        Ok((success, stderr_tail)) => TaskOutcome {
+            name: task.name,
+            cmd: task.cmd,
+            started_at,
+            ended_at: SystemTime::now(),
+            success,
+            duration: start.elapsed(),
+            stderr_tail,
+        },
+        Err(err) => {
+            eprintln!("remote task \"{}\" failed: {err}", task.name);
+            TaskOutcome {
+                name: task.name,
+                cmd: task.cmd,
+                started_at,
+                ended_at: SystemTime::now(),
+                success: false,
+                duration: start.elapsed(),
+                stderr_tail: err.to_string(),
+            }
+        }
+    }
+}
+
+async fn dispatch(
+    task: &Task,
+    host: &str,
+    token: Option<String>,
+    cancellation_token: CancellationToken,
+) -> Result<(bool, String), Error> {
+    let stream = TcpStream::connect(host).await?;
+    let (read_half, mut write_half) = stream.into_split();
+
+    let mut request = serde_json::to_string(&ClientMessage::Run {
+        task: task.clone(),
+        token,
+    })?;
+    request.push('\n');
+    write_half.write_all(request.as_bytes()).await?;
+
+    let name = task.name.clone();
+    let mut lines = BufReader::new(read_half).lines();
+
+    loop {
+        tokio::select! {
+            _ = cancellation_token.cancelled() => {
+                let mut cancel = serde_json::to_string(&ClientMessage::Cancel)?;
+                cancel.push('\n');
+                let _ = write_half.write_all(cancel.as_bytes()).await;
+                return Ok((false, String::new()));
+            }
+            line = lines.next_line() => {
+                let Some(line) = line? else {
+                    return Ok((false, String::new()));
+                };
+                match serde_json::from_str::<ServerMessage>(&line)? {
+                    ServerMessage::Line(line) => println!("[{name}] {line}"),
+                    ServerMessage::Exit { success, stderr_tail } => return Ok((success, stderr_tail)),
+                }
+            }
+        }
+    }
+}
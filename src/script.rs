@@ -0,0 +1,85 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use mlua::{Lua, LuaOptions, StdLib, Table};
+
+use crate::Task;
+
+type Error = Box<dyn std::error::Error>;
+
+pub const RUNNER_LUA: &str = "runner.lua";
+
+/// Evaluates `runner.lua` in a sandboxed Lua interpreter, exposing `task(name,
+/// cmd)` and `build(name, cmd, {depends_on=..., artifacts=...})` globals that
+/// append to the returned task/build lists, plus `env` (a table of the
+/// process's environment variables) and `OS` (the detected operating system,
+/// e.g. "linux"/"macos"/"windows").
+pub fn load(path: &Path) -> Result<(Vec<Task>, Vec<Task>), Error> {
+    let source = std::fs::read_to_string(path)?;
+    let stdlib = StdLib::ALL_SAFE - StdLib::OS - StdLib::IO;
+    let lua = Lua::new_with(stdlib, LuaOptions::default())?;
+
+    let tasks: Arc<Mutex<Vec<Task>>> = Arc::new(Mutex::new(Vec::new()));
+    let builds: Arc<Mutex<Vec<Task>>> = Arc::new(Mutex::new(Vec::new()));
+
+    {
+        let tasks = tasks.clone();
+        let task_fn = lua.create_function(move |_, (name, cmd): (String, String)| {
+            tasks.lock().unwrap().push(Task {
+                name,
+                cmd,
+                depends_on: Vec::new(),
+                artifacts: Vec::new(),
+            });
+            Ok(())
+        })?;
+        lua.globals().set("task", task_fn)?;
+    }
+
+    {
+        let builds = builds.clone();
+        let build_fn =
+            lua.create_function(move |_, (name, cmd, opts): (String, String, Option<Table>)| {
+                let (depends_on, artifacts) = match opts {
+                    Some(opts) => (
+                        table_of_strings(&opts, "depends_on")?,
+                        table_of_strings(&opts, "artifacts")?,
+                    ),
+                    None => (Vec::new(), Vec::new()),
+                };
+                builds.lock().unwrap().push(Task {
+                    name,
+                    cmd,
+                    depends_on,
+                    artifacts,
+                });
+                Ok(())
+            })?;
+        lua.globals().set("build", build_fn)?;
+    }
+
+    let env = lua.create_table()?;
+    for (key, value) in std::env::vars() {
+        env.set(key, value)?;
+    }
+    lua.globals().set("env", env)?;
+    lua.globals().set("OS", std::env::consts::OS)?;
+
+    lua.load(&source).exec()?;
+
+    let tasks = Arc::try_unwrap(tasks)
+        .map_err(|_| "runner.lua task list still in use")?
+        .into_inner()?;
+    let builds = Arc::try_unwrap(builds)
+        .map_err(|_| "runner.lua build list still in use")?
+        .into_inner()?;
+
+    Ok((tasks, builds))
+}
+
+fn table_of_strings(opts: &Table, key: &str) -> mlua::Result<Vec<String>> {
+    match opts.get::<_, Option<Table>>(key)? {
+        Some(table) => table.sequence_values::<String>().collect(),
+        None => Ok(Vec::new()),
+    }
+}